@@ -12,7 +12,9 @@ pub enum Error {
     /// Tried to create a fuel level with a negative number
     NegativeFuelLevel,
     /// Tried to create NaN or Infinite fuel
-    InvalidFuelLevel
+    InvalidFuelLevel,
+    /// Tried to add fuel beyond the tank's capacity
+    TankOverflow
 }
 
 /// Represents a fuel level in the tank
@@ -78,18 +80,86 @@ impl FuelLevel {
     pub const fn as_millilitres(self) -> f64 {
         self.level_litres * 1000.0
     }
+
+    /// Subtract `other`, flooring at [`FuelLevel::zero`] instead of panicking
+    pub fn saturating_sub(self, other: FuelLevel) -> FuelLevel {
+        if self.level_litres <= other.level_litres {
+            FuelLevel::zero()
+        } else {
+            FuelLevel::with_litres(self.level_litres - other.level_litres)
+        }
+    }
+
+    /// Add `other`, capping at [`FUEL_LEVEL_MAX`] instead of overflowing the tank
+    pub fn saturating_add(self, other: FuelLevel) -> FuelLevel {
+        let sum = FuelLevel::with_litres(self.level_litres + other.level_litres);
+        if sum > FUEL_LEVEL_MAX {
+            FUEL_LEVEL_MAX
+        } else {
+            sum
+        }
+    }
+
+    /// Subtract `other`, or `Err(Error::NegativeFuelLevel)` if it would go below zero
+    pub fn checked_sub(self, other: FuelLevel) -> Result<FuelLevel, Error> {
+        if self.level_litres < other.level_litres {
+            Err(Error::NegativeFuelLevel)
+        } else {
+            Ok(FuelLevel::with_litres(self.level_litres - other.level_litres))
+        }
+    }
+
+    /// Add `other`, or `Err(Error::TankOverflow)` if it would exceed [`FUEL_LEVEL_MAX`]
+    pub fn checked_add(self, other: FuelLevel) -> Result<FuelLevel, Error> {
+        let sum = FuelLevel::with_litres(self.level_litres + other.level_litres);
+        if sum > FUEL_LEVEL_MAX {
+            Err(Error::TankOverflow)
+        } else {
+            Ok(sum)
+        }
+    }
+}
+
+/// How a [`FuelMonitor`] aggregates its window of readings into a mean
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum AggregationMode {
+    /// A flat arithmetic mean over the whole window (the default)
+    Flat,
+    /// An exponentially weighted mean that smooths out sensor noise while
+    /// favouring fresh data: each older reading contributes a factor of
+    /// `alpha` less than the reading after it
+    ExponentialRecent {
+        /// Decay applied per reading, in `(0.0, 1.0]`
+        alpha: f64,
+    },
+    /// A flat mean over only the `n` most recent readings
+    WindowedLast {
+        /// Number of most-recent readings to average
+        n: usize,
+    },
 }
 
 /// Tracks recentl fuel readings
 pub struct FuelMonitor {
-    levels: heapless::HistoryBuf<FuelLevel, 16>
+    levels: heapless::HistoryBuf<FuelLevel, 16>,
+    mode: AggregationMode
 }
 
 impl FuelMonitor {
     /// Create a new fuel monitor with no readings
     pub fn new() -> FuelMonitor {
         FuelMonitor {
-            levels: heapless::HistoryBuf::new()
+            levels: heapless::HistoryBuf::new(),
+            mode: AggregationMode::Flat
+        }
+    }
+
+    /// Create a new fuel monitor with no readings that aggregates its
+    /// window using `mode` instead of a flat mean
+    pub fn with_mode(mode: AggregationMode) -> FuelMonitor {
+        FuelMonitor {
+            levels: heapless::HistoryBuf::new(),
+            mode
         }
     }
 
@@ -108,16 +178,180 @@ impl FuelMonitor {
         self.levels.oldest_ordered().max().cloned()
     }
 
-    /// Get the mean fuel level
+    /// Get the mean fuel level, aggregated according to the configured
+    /// [`AggregationMode`]
     pub fn mean(&self) -> Option<FuelLevel> {
+        match self.mode {
+            AggregationMode::Flat => self.flat_mean(),
+            AggregationMode::ExponentialRecent { alpha } => self.exponential_mean(alpha),
+            AggregationMode::WindowedLast { n } => self.windowed_mean(n),
+        }
+    }
+
+    fn flat_mean(&self) -> Option<FuelLevel> {
         if self.levels.len() == 0 {
             return None;
         }
-        let mut total = 0.0;
+        let total_ml = self.total_millilitres();
+        FuelLevel::with_millilitres(total_ml as f64 / self.levels.len() as f64).ok()
+    }
+
+    /// The sum of every reading in the window, in whole millilitres
+    ///
+    /// Summed as an integer accumulator rather than `f64` litres, so there's
+    /// no rounding drift on FPU-less targets. Saturates instead of panicking
+    /// if the total ever exceeds `u64::MAX`.
+    pub fn total_millilitres(&self) -> u64 {
+        let mut total: u64 = 0;
         for level in self.levels.oldest_ordered() {
-            total = total + level.as_litres();
+            let ml = level.as_millilitres().round() as u64;
+            total = total.saturating_add(ml);
+        }
+        total
+    }
+
+    fn exponential_mean(&self, alpha: f64) -> Option<FuelLevel> {
+        let len = self.levels.len();
+        if len == 0 {
+            return None;
+        }
+        let mut weighted_total = 0.0;
+        let mut weight_total = 0.0;
+        for (i, level) in self.levels.oldest_ordered().enumerate() {
+            let weight = alpha.powi((len - 1 - i) as i32);
+            weighted_total += level.as_litres() * weight;
+            weight_total += weight;
         }
-        Some(FuelLevel::with_litres(total / self.levels.len() as f64))
+        Some(FuelLevel::with_litres(weighted_total / weight_total))
+    }
+
+    fn windowed_mean(&self, n: usize) -> Option<FuelLevel> {
+        let len = self.levels.len();
+        if len == 0 {
+            return None;
+        }
+        let skip = len.saturating_sub(n);
+        let mut total = 0.0;
+        let mut count = 0usize;
+        for level in self.levels.oldest_ordered().skip(skip) {
+            total += level.as_litres();
+            count += 1;
+        }
+        if count == 0 {
+            return None;
+        }
+        Some(FuelLevel::with_litres(total / count as f64))
+    }
+
+    /// The mean change in level between successive readings that burned
+    /// fuel, in litres; the value is negative (a drop of 1.5 litres per
+    /// reading is `-1.5`)
+    ///
+    /// Only negative deltas (fuel burned) count towards the average; refuel
+    /// events are ignored. Returns `None` if there are fewer than two
+    /// readings, or if nothing has ever been burned.
+    pub fn consumption_rate_per_reading(&self) -> Option<f64> {
+        let mut total = 0.0;
+        let mut count = 0u32;
+        let mut iter = self.levels.oldest_ordered();
+        let mut prev = iter.next()?;
+        for level in iter {
+            let delta = level.as_litres() - prev.as_litres();
+            if delta < 0.0 {
+                total += delta;
+                count += 1;
+            }
+            prev = level;
+        }
+        if count == 0 {
+            return None;
+        }
+        Some(total / count as f64)
+    }
+
+    /// The fuel level reached at the most recent refuel event, if any
+    pub fn last_refuel(&self) -> Option<FuelLevel> {
+        let mut last_refuel = None;
+        let mut iter = self.levels.oldest_ordered();
+        let mut prev = iter.next()?;
+        for level in iter {
+            let delta = level.as_litres() - prev.as_litres();
+            if delta > 0.0 {
+                last_refuel = Some(*level);
+            }
+            prev = level;
+        }
+        last_refuel
+    }
+
+    /// Estimate how many more readings remain before the tank runs dry
+    ///
+    /// Divides the current level by the average burn rate from
+    /// [`FuelMonitor::consumption_rate_per_reading`]. Returns `None` if the
+    /// most recent reading did not drop below the one before it (a refuel or
+    /// an unchanged level, rather than the tank being drained), or if the
+    /// burn rate is too small to divide by.
+    pub fn readings_until_empty(&self) -> Option<u32> {
+        let mut iter = self.levels.oldest_ordered();
+        let current = iter.next_back()?;
+        let previous = iter.next_back()?;
+        if current.as_litres() >= previous.as_litres() {
+            return None;
+        }
+        let rate = self.consumption_rate_per_reading()?;
+        let burn_per_reading = -rate;
+        if burn_per_reading <= 0.0 {
+            return None;
+        }
+        Some((current.as_litres() / burn_per_reading) as u32)
+    }
+}
+
+/// The state of a [`FuelAlarm`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub enum AlarmState {
+    /// Fuel level is within acceptable bounds
+    Clear,
+    /// Fuel level has dropped below the low threshold and has not yet
+    /// risen back above the clear threshold
+    Triggered,
+}
+
+/// A latched low-fuel alarm with hysteresis
+///
+/// Modeled on the rustc optimization-fuel struct, but extended so a level
+/// hovering near the threshold doesn't chatter the alarm on and off: once
+/// triggered, the alarm stays triggered until the level climbs back above
+/// `clear`.
+pub struct FuelAlarm {
+    low: FuelLevel,
+    clear: FuelLevel,
+    state: AlarmState,
+}
+
+impl FuelAlarm {
+    /// Create a new alarm that triggers below `low` and clears above `clear`
+    pub fn new(low: FuelLevel, clear: FuelLevel) -> FuelAlarm {
+        FuelAlarm {
+            low,
+            clear,
+            state: AlarmState::Clear,
+        }
+    }
+
+    /// Feed the alarm a new reading, returning its (possibly updated) state
+    pub fn update(&mut self, level: FuelLevel) -> AlarmState {
+        self.state = match self.state {
+            AlarmState::Clear if level < self.low => AlarmState::Triggered,
+            AlarmState::Triggered if level > self.clear => AlarmState::Clear,
+            state => state,
+        };
+        self.state
+    }
+
+    /// The alarm's current state, without feeding it a new reading
+    pub fn state(&self) -> AlarmState {
+        self.state
     }
 }
 
@@ -151,6 +385,50 @@ mod tests {
         assert_eq!(level, Ok(FuelLevel::with_litres(1.0)));
     }
 
+    #[test]
+    fn saturating_sub_floors_at_zero() {
+        let level = FuelLevel::with_litres(1.0);
+        assert_eq!(
+            level.saturating_sub(FuelLevel::with_litres(5.0)),
+            FuelLevel::zero()
+        );
+    }
+
+    #[test]
+    fn saturating_add_caps_at_max() {
+        let level = FuelLevel::with_litres(8.0);
+        assert_eq!(
+            level.saturating_add(FuelLevel::with_litres(5.0)),
+            FUEL_LEVEL_MAX
+        );
+    }
+
+    #[test]
+    fn checked_sub_reports_underflow() {
+        let level = FuelLevel::with_litres(1.0);
+        assert_eq!(
+            level.checked_sub(FuelLevel::with_litres(5.0)),
+            Err(Error::NegativeFuelLevel)
+        );
+        assert_eq!(
+            level.checked_sub(FuelLevel::with_litres(1.0)),
+            Ok(FuelLevel::zero())
+        );
+    }
+
+    #[test]
+    fn checked_add_reports_overflow() {
+        let level = FuelLevel::with_litres(8.0);
+        assert_eq!(
+            level.checked_add(FuelLevel::with_litres(5.0)),
+            Err(Error::TankOverflow)
+        );
+        assert_eq!(
+            level.checked_add(FuelLevel::with_litres(1.0)),
+            Ok(FuelLevel::with_litres(9.0))
+        );
+    }
+
     #[test]
     fn insert_into_monitor() {
         let mut monitor = FuelMonitor::new();
@@ -167,4 +445,113 @@ mod tests {
         assert_eq!(monitor.max(), Some(FuelLevel::with_litres(3.0)));
         assert_eq!(monitor.mean(), Some(FuelLevel::with_litres(2.0)));
     }
+
+    #[test]
+    fn consumption_rate_needs_two_readings() {
+        let mut monitor = FuelMonitor::new();
+        assert_eq!(monitor.consumption_rate_per_reading(), None);
+        monitor.insert(FuelLevel::with_litres(1.0));
+        assert_eq!(monitor.consumption_rate_per_reading(), None);
+    }
+
+    #[test]
+    fn consumption_rate_ignores_refuels_and_zero_deltas() {
+        let mut monitor = FuelMonitor::new();
+        monitor.insert(FuelLevel::with_litres(10.0));
+        monitor.insert(FuelLevel::with_litres(9.0));
+        monitor.insert(FuelLevel::with_litres(9.0));
+        monitor.insert(FuelLevel::with_litres(7.0));
+        monitor.insert(FuelLevel::with_litres(9.0));
+        assert_eq!(monitor.consumption_rate_per_reading(), Some(-1.5));
+        assert_eq!(monitor.last_refuel(), Some(FuelLevel::with_litres(9.0)));
+    }
+
+    #[test]
+    fn readings_until_empty_predicts_range() {
+        let mut monitor = FuelMonitor::new();
+        monitor.insert(FuelLevel::with_litres(10.0));
+        monitor.insert(FuelLevel::with_litres(8.0));
+        assert_eq!(monitor.readings_until_empty(), Some(4));
+    }
+
+    #[test]
+    fn readings_until_empty_is_none_while_refuelling() {
+        let mut monitor = FuelMonitor::new();
+        monitor.insert(FuelLevel::with_litres(1.0));
+        monitor.insert(FuelLevel::with_litres(2.0));
+        assert_eq!(monitor.readings_until_empty(), None);
+    }
+
+    #[test]
+    fn readings_until_empty_is_none_right_after_a_refuel() {
+        let mut monitor = FuelMonitor::new();
+        monitor.insert(FuelLevel::with_litres(10.0));
+        monitor.insert(FuelLevel::with_litres(8.0));
+        monitor.insert(FuelLevel::with_litres(9.0));
+        assert_eq!(monitor.readings_until_empty(), None);
+    }
+
+    #[test]
+    fn alarm_starts_clear() {
+        let alarm = FuelAlarm::new(FuelLevel::with_litres(2.0), FuelLevel::with_litres(3.0));
+        assert_eq!(alarm.state(), AlarmState::Clear);
+    }
+
+    #[test]
+    fn alarm_triggers_below_low_and_has_hysteresis() {
+        let mut alarm = FuelAlarm::new(FuelLevel::with_litres(2.0), FuelLevel::with_litres(3.0));
+        assert_eq!(alarm.update(FuelLevel::with_litres(5.0)), AlarmState::Clear);
+        assert_eq!(alarm.update(FuelLevel::with_litres(1.0)), AlarmState::Triggered);
+        // Still below the clear threshold, so the alarm stays latched
+        assert_eq!(alarm.update(FuelLevel::with_litres(2.5)), AlarmState::Triggered);
+        assert_eq!(alarm.update(FuelLevel::with_litres(3.5)), AlarmState::Clear);
+    }
+
+    #[test]
+    fn flat_mode_matches_default() {
+        let mut monitor = FuelMonitor::with_mode(AggregationMode::Flat);
+        monitor.insert(FuelLevel::with_litres(1.0));
+        monitor.insert(FuelLevel::with_litres(3.0));
+        assert_eq!(monitor.mean(), Some(FuelLevel::with_litres(2.0)));
+    }
+
+    #[test]
+    fn windowed_mode_only_averages_last_n() {
+        let mut monitor = FuelMonitor::with_mode(AggregationMode::WindowedLast { n: 2 });
+        monitor.insert(FuelLevel::with_litres(10.0));
+        monitor.insert(FuelLevel::with_litres(2.0));
+        monitor.insert(FuelLevel::with_litres(4.0));
+        assert_eq!(monitor.mean(), Some(FuelLevel::with_litres(3.0)));
+    }
+
+    #[test]
+    fn windowed_mode_with_zero_window_is_none() {
+        let mut monitor = FuelMonitor::with_mode(AggregationMode::WindowedLast { n: 0 });
+        monitor.insert(FuelLevel::with_litres(1.0));
+        assert_eq!(monitor.mean(), None);
+    }
+
+    #[test]
+    fn exponential_mode_favours_recent_readings() {
+        let mut monitor = FuelMonitor::with_mode(AggregationMode::ExponentialRecent { alpha: 0.5 });
+        monitor.insert(FuelLevel::with_litres(0.0));
+        monitor.insert(FuelLevel::with_litres(10.0));
+        // weights from oldest to newest are 0.5 and 1.0
+        assert_eq!(monitor.mean(), Some(FuelLevel::with_litres(10.0 / 1.5)));
+    }
+
+    #[test]
+    fn total_millilitres_sums_exactly() {
+        let mut monitor = FuelMonitor::new();
+        monitor.insert(FuelLevel::with_litres(1.5));
+        monitor.insert(FuelLevel::with_litres(2.25));
+        assert_eq!(monitor.total_millilitres(), 3750);
+        assert_eq!(monitor.mean(), Some(FuelLevel::with_litres(1.875)));
+    }
+
+    #[test]
+    fn total_millilitres_is_zero_when_empty() {
+        let monitor = FuelMonitor::new();
+        assert_eq!(monitor.total_millilitres(), 0);
+    }
 }
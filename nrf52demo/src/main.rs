@@ -4,7 +4,7 @@
 use cortex_m as _;
 use defmt::println;
 use defmt_rtt as _;
-use fuel_monitor::{FuelLevel, FuelMonitor};
+use fuel_monitor::{AlarmState, FuelAlarm, FuelLevel, FuelMonitor};
 
 const GPIO_P0_BASE: *mut u32 = 0x5000_0500 as *mut u32;
 
@@ -19,6 +19,8 @@ fn main() -> ! {
     let minimum = monitor.min();
     let maximum = monitor.max();
 
+    let mut alarm = FuelAlarm::new(FuelLevel::with_litres(2.0), FuelLevel::with_litres(3.0));
+
     unsafe {
         GPIO_P0_BASE
             .byte_offset(DIRSET_OFFSET)
@@ -28,18 +30,19 @@ fn main() -> ! {
     println!("Hello, world! min={}, max={}", minimum, maximum);
 
     loop {
-        unsafe {
-            GPIO_P0_BASE
-                .byte_offset(OUTSET_OFFSET)
-                .write_volatile(1 << 13);
-        }
-
-        cortex_m::asm::delay(1_000_000);
-
-        unsafe {
-            GPIO_P0_BASE
-                .byte_offset(OUTCLR_OFFSET)
-                .write_volatile(1 << 13);
+        let level = monitor.mean().unwrap_or(FuelLevel::zero());
+
+        match alarm.update(level) {
+            AlarmState::Triggered => unsafe {
+                GPIO_P0_BASE
+                    .byte_offset(OUTSET_OFFSET)
+                    .write_volatile(1 << 13);
+            },
+            AlarmState::Clear => unsafe {
+                GPIO_P0_BASE
+                    .byte_offset(OUTCLR_OFFSET)
+                    .write_volatile(1 << 13);
+            },
         }
 
         cortex_m::asm::delay(1_000_000);